@@ -1,37 +1,366 @@
 use std::iter;
+use std::sync::Arc;
+use std::rc::Rc;
+use std::cell::Cell;
+
+mod texture;
+use texture::Texture;
+
+mod renderer;
+pub use renderer::{ Renderer, RenderPass };
+
+mod passes;
+use passes::{ GeometryPass, TonemapPass, SharedClearColor };
 
 use winit::{
     dpi::{ LogicalSize, PhysicalSize }, event::*, event_loop::EventLoop, keyboard::{KeyCode, PhysicalKey}, window::{ Window, WindowBuilder }
 };
 use wgpu::{
-    Surface, Device, Queue, SurfaceConfiguration, Instance, InstanceDescriptor,
+    Surface, Device, Queue, SurfaceConfiguration, InstanceDescriptor,
     Backends, RequestAdapterOptions, PowerPreference, DeviceDescriptor,
     Features, Limits, TextureUsages, SurfaceError, TextureViewDescriptor,
     CommandEncoderDescriptor, RenderPassDescriptor, RenderPassColorAttachment,
-    Operations, LoadOp, Color, StoreOp,
+    Operations, LoadOp, Color, StoreOp, RenderPipeline, PipelineLayoutDescriptor,
+    ShaderModuleDescriptor, ShaderSource, VertexState, FragmentState,
+    ColorTargetState, ColorWrites, PrimitiveState, PrimitiveTopology, FrontFace,
+    Face, PolygonMode, MultisampleState, TextureFormat, Buffer, BufferUsages,
+    VertexBufferLayout, VertexAttribute, VertexStepMode,
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
+    BufferBindingType, ShaderStages, BindGroup, BindGroupDescriptor, BindGroupEntry,
+    Texture as WgpuTexture, TextureDescriptor, TextureDimension, Extent3d, TextureView,
+    DepthStencilState, CompareFunction, StencilState, DepthBiasState,
+    TextureSampleType, SamplerBindingType, TextureViewDimension,
+    Sampler, SamplerDescriptor, AddressMode, FilterMode, AdapterInfo,
 };
+use wgpu::util::{ DeviceExt, BufferInitDescriptor };
+use egui_wgpu::{ Renderer as EguiRenderer, ScreenDescriptor };
+use cgmath::prelude::*;
 use log::{ info, error };
+use cfg_if::cfg_if;
+
+/// wgpu's clip-space z range is 0..1, while cgmath (like OpenGL) assumes
+/// -1..1, so every projection matrix needs this correction baked in.
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+/// The shader that ships with the crate, used unless a caller swaps it out
+/// via [`GraphicsState::set_shader_source`].
+const DEFAULT_SHADER_SOURCE: &str = include_str!("shader.wgsl");
+
+const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+/// The texture the crate ships with, sampled onto the demo pentagon.
+const DEFAULT_TEXTURE_BYTES: &[u8] = include_bytes!("res/default_texture.png");
+
+/// The scene renders into this offscreen format so that highlights above
+/// 1.0 survive until the tonemap resolve pass.
+const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Shader for the fullscreen tonemap resolve pass.
+const TONEMAP_SHADER_SOURCE: &str = include_str!("tonemap.wgsl");
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+}
+
+impl Vertex {
+    const ATTRIBS: [VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x2,
+    ];
+
+    fn desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+const VERTICES: &[Vertex] = &[
+    Vertex { position: [-0.0868241, 0.49240386, 0.0], tex_coords: [0.4131759, 0.00759614] },
+    Vertex { position: [-0.49513406, 0.06958647, 0.0], tex_coords: [0.0048659444, 0.43041354] },
+    Vertex { position: [-0.21918549, -0.44939706, 0.0], tex_coords: [0.28081453, 0.949397] },
+    Vertex { position: [0.35966998, -0.3473291, 0.0], tex_coords: [0.85967, 0.84732914] },
+    Vertex { position: [0.44147372, 0.2347359, 0.0], tex_coords: [0.9414737, 0.2652641] },
+];
+
+const INDICES: &[u16] = &[
+    0, 1, 4,
+    1, 2, 4,
+    2, 3, 4,
+];
+
+const NUM_INSTANCES_PER_ROW: u32 = 10;
+const INSTANCE_SPACING: f32 = 3.0;
+
+/// A single copy of the mesh to draw, placed and oriented in world space.
+/// Packed down to [`InstanceRaw`] before upload since shaders can't consume
+/// `cgmath` types directly.
+pub struct Instance {
+    pub position: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+}
+
+impl Instance {
+    fn to_raw(&self) -> InstanceRaw {
+        InstanceRaw {
+            model: (cgmath::Matrix4::from_translation(self.position)
+                * cgmath::Matrix4::from(self.rotation)).into(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct InstanceRaw {
+    model: [[f32; 4]; 4],
+}
+
+impl InstanceRaw {
+    const ATTRIBS: [VertexAttribute; 4] = wgpu::vertex_attr_array![
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4,
+    ];
+
+    fn desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+/// Lays out `NUM_INSTANCES_PER_ROW x NUM_INSTANCES_PER_ROW` copies of the
+/// mesh on a flat grid, centered on the origin, for throughput testing. The
+/// default instance layout `GraphicsState::new()` starts with; pass a
+/// different `Vec<Instance>` to [`GraphicsState::set_instances`] for a
+/// custom layout.
+pub fn generate_grid_instances() -> Vec<Instance> {
+    (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
+        (0..NUM_INSTANCES_PER_ROW).map(move |x| {
+            let row_offset = (NUM_INSTANCES_PER_ROW as f32 - 1.0) * INSTANCE_SPACING / 2.0;
+
+            let position = cgmath::Vector3 {
+                x: x as f32 * INSTANCE_SPACING - row_offset,
+                y: 0.0,
+                z: z as f32 * INSTANCE_SPACING - row_offset,
+            };
+
+            let rotation = if position.is_zero() {
+                cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
+            } else {
+                cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
+            };
+
+            Instance { position, rotation }
+        })
+    }).collect()
+}
+
+struct Camera {
+    eye: cgmath::Point3<f32>,
+    target: cgmath::Point3<f32>,
+    up: cgmath::Vector3<f32>,
+    aspect: f32,
+    fovy: f32,
+    znear: f32,
+    zfar: f32,
+}
+
+impl Camera {
+    fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
+        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
+
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+impl CameraUniform {
+    fn new() -> Self {
+        Self { view_proj: cgmath::Matrix4::identity().into() }
+    }
+
+    fn update_view_proj(&mut self, camera: &Camera) {
+        self.view_proj = camera.build_view_projection_matrix().into();
+    }
+}
+
+struct CameraController {
+    speed: f32,
+    is_up_pressed: bool,
+    is_down_pressed: bool,
+    is_forward_pressed: bool,
+    is_backward_pressed: bool,
+    is_left_pressed: bool,
+    is_right_pressed: bool,
+}
+
+impl CameraController {
+    fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            is_up_pressed: false,
+            is_down_pressed: false,
+            is_forward_pressed: false,
+            is_backward_pressed: false,
+            is_left_pressed: false,
+            is_right_pressed: false,
+        }
+    }
+
+    fn process_events(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    state,
+                    physical_key: PhysicalKey::Code(keycode),
+                    ..
+                },
+                ..
+            } => {
+                let is_pressed = *state == ElementState::Pressed;
+
+                match keycode {
+                    KeyCode::Space => {
+                        self.is_up_pressed = is_pressed;
+                        true
+                    }
+                    KeyCode::ShiftLeft => {
+                        self.is_down_pressed = is_pressed;
+                        true
+                    }
+                    KeyCode::KeyW | KeyCode::ArrowUp => {
+                        self.is_forward_pressed = is_pressed;
+                        true
+                    }
+                    KeyCode::KeyA | KeyCode::ArrowLeft => {
+                        self.is_left_pressed = is_pressed;
+                        true
+                    }
+                    KeyCode::KeyS | KeyCode::ArrowDown => {
+                        self.is_backward_pressed = is_pressed;
+                        true
+                    }
+                    KeyCode::KeyD | KeyCode::ArrowRight => {
+                        self.is_right_pressed = is_pressed;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    fn update_camera(&self, camera: &mut Camera) {
+        let forward = camera.target - camera.eye;
+        let forward_norm = forward.normalize();
+        let forward_mag = forward.magnitude();
+
+        if self.is_forward_pressed && forward_mag > self.speed {
+            camera.eye += forward_norm * self.speed;
+        }
+        if self.is_backward_pressed {
+            camera.eye -= forward_norm * self.speed;
+        }
+
+        let right = forward_norm.cross(camera.up);
+
+        let forward = camera.target - camera.eye;
+        let forward_mag = forward.magnitude();
+
+        if self.is_right_pressed {
+            camera.eye = camera.target - (forward + right * self.speed).normalize() * forward_mag;
+        }
+        if self.is_left_pressed {
+            camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
+        }
+        if self.is_up_pressed {
+            camera.eye.y += self.speed;
+        }
+        if self.is_down_pressed {
+            camera.eye.y -= self.speed;
+        }
+    }
+}
 
 enum UpdateType {
     Pos((u16, u16)),
-    NONE,
+    None,
 }
 
-struct GraphicsState<'a> {
+/// Owns every GPU resource for one window. `new()` is the crate's real
+/// library entry point — [`run()`] is just a demo built on top of it, so a
+/// caller can drive its own event loop and reach [`Self::renderer_mut`],
+/// [`Self::set_shader_source`], and [`Self::set_instances`] directly.
+pub struct GraphicsState<'a> {
     surface: Surface<'a>,
-    device: Device,
-    queue: Queue,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
     config: SurfaceConfiguration,
     size: PhysicalSize<u32>,
     window: &'a Window,
     pos: (u16, u16),
+    render_pipeline: Arc<RenderPipeline>,
+    vertex_buffer: Arc<Buffer>,
+    index_buffer: Arc<Buffer>,
+    num_indices: u32,
+    camera: Camera,
+    camera_uniform: CameraUniform,
+    camera_buffer: Buffer,
+    camera_bind_group: Arc<BindGroup>,
+    camera_bind_group_layout: BindGroupLayout,
+    camera_controller: CameraController,
+    depth_texture: WgpuTexture,
+    depth_view: Arc<TextureView>,
+    // Never read directly, but must outlive `diffuse_bind_group`.
+    #[allow(dead_code)]
+    diffuse_texture: Texture,
+    diffuse_bind_group: Arc<BindGroup>,
+    texture_bind_group_layout: BindGroupLayout,
+    instances: Vec<Instance>,
+    instance_buffer: Arc<Buffer>,
+    hdr_texture: WgpuTexture,
+    hdr_view: Arc<TextureView>,
+    hdr_sampler: Sampler,
+    hdr_bind_group: Arc<BindGroup>,
+    hdr_bind_group_layout: BindGroupLayout,
+    tonemap_pipeline: Arc<RenderPipeline>,
+    adapter_info: AdapterInfo,
+    egui_winit_state: egui_winit::State,
+    egui_renderer: EguiRenderer,
+    clear_color_b: f32,
+    last_frame: std::time::Instant,
+    fps: f32,
+    clear_color: SharedClearColor,
+    renderer: Renderer,
 }
 
 impl<'a> GraphicsState<'a> {
     pub async fn new(window: &'a Window) -> GraphicsState<'a> {
         let size = window.inner_size();
 
-        let instance = Instance::new(InstanceDescriptor {
+        let instance = wgpu::Instance::new(InstanceDescriptor {
             #[cfg(not(target_arch="wasm32"))]
             backends: Backends::PRIMARY,
             #[cfg(target_arch="wasm32")]
@@ -58,11 +387,15 @@ impl<'a> GraphicsState<'a> {
                     Limits::default()
                 },
                 label: None,
-                memory_hints: Default::default(),
             },
             None,
         ).await.unwrap();
 
+        let device = Arc::new(device);
+        let queue = Arc::new(queue);
+
+        let adapter_info = adapter.get_info();
+
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps.formats.iter()
             .find(|f| f.is_srgb())
@@ -82,7 +415,115 @@ impl<'a> GraphicsState<'a> {
 
         surface.configure(&device, &config);
 
-        Self {
+        let camera = Camera {
+            eye: (0.0, 1.0, 2.0).into(),
+            target: (0.0, 0.0, 0.0).into(),
+            up: cgmath::Vector3::unit_y(),
+            aspect: config.width as f32 / config.height as f32,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 100.0,
+        };
+
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_proj(&camera);
+
+        let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera_uniform]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let camera_bind_group_layout = Self::create_camera_bind_group_layout(&device);
+
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &camera_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("Camera Bind Group"),
+        });
+
+        let camera_controller = CameraController::new(0.02);
+
+        let texture_bind_group_layout = Self::create_texture_bind_group_layout(&device);
+
+        let diffuse_texture = Texture::from_bytes(
+            &device, &queue, DEFAULT_TEXTURE_BYTES, "Default Texture",
+        ).unwrap();
+
+        let diffuse_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &texture_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                },
+            ],
+            label: Some("Diffuse Bind Group"),
+        });
+
+        let render_pipeline = Self::create_render_pipeline(
+            &device, HDR_FORMAT, DEFAULT_SHADER_SOURCE,
+            &camera_bind_group_layout, &texture_bind_group_layout,
+        );
+
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(VERTICES),
+            usage: BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: bytemuck::cast_slice(INDICES),
+            usage: BufferUsages::INDEX,
+        });
+
+        let num_indices = INDICES.len() as u32;
+
+        let (depth_texture, depth_view) = Self::create_depth_texture(&device, &config);
+
+        let instances = generate_grid_instances();
+        let instance_buffer = Self::create_instance_buffer(&device, &instances);
+
+        let (hdr_texture, hdr_view, hdr_sampler) = Self::create_hdr_resources(&device, &config);
+        let hdr_bind_group_layout = Self::create_texture_bind_group_layout(&device);
+        let hdr_bind_group = Self::create_hdr_bind_group(
+            &device, &hdr_bind_group_layout, &hdr_view, &hdr_sampler,
+        );
+        let tonemap_pipeline = Self::create_tonemap_pipeline(
+            &device, surface_format, &hdr_bind_group_layout,
+        );
+
+        let egui_winit_state = egui_winit::State::new(
+            egui::Context::default(), egui::ViewportId::ROOT, window, None, None,
+        );
+        let egui_renderer = EguiRenderer::new(&device, surface_format, None, 1);
+
+        let renderer = Renderer::new(device.clone(), queue.clone());
+
+        // wgpu's resource handles (`Buffer`, `BindGroup`, `RenderPipeline`,
+        // `TextureView`, ...) don't implement `Clone`, but `rebuild_renderer`
+        // needs to hand the passes their own references to the same GPU
+        // objects, so everything it shares is `Arc`-wrapped once here.
+        let render_pipeline = Arc::new(render_pipeline);
+        let vertex_buffer = Arc::new(vertex_buffer);
+        let index_buffer = Arc::new(index_buffer);
+        let camera_bind_group = Arc::new(camera_bind_group);
+        let diffuse_bind_group = Arc::new(diffuse_bind_group);
+        let instance_buffer = Arc::new(instance_buffer);
+        let depth_view = Arc::new(depth_view);
+        let hdr_view = Arc::new(hdr_view);
+        let hdr_bind_group = Arc::new(hdr_bind_group);
+        let tonemap_pipeline = Arc::new(tonemap_pipeline);
+
+        let mut graphics_state = Self {
             window,
             surface,
             device,
@@ -90,10 +531,371 @@ impl<'a> GraphicsState<'a> {
             config,
             size,
             pos: (0, 0),
-        }
+            render_pipeline,
+            vertex_buffer,
+            index_buffer,
+            num_indices,
+            camera,
+            camera_uniform,
+            camera_buffer,
+            camera_bind_group,
+            camera_bind_group_layout,
+            camera_controller,
+            depth_texture,
+            depth_view,
+            diffuse_texture,
+            diffuse_bind_group,
+            texture_bind_group_layout,
+            instances,
+            instance_buffer,
+            hdr_texture,
+            hdr_view,
+            hdr_sampler,
+            hdr_bind_group,
+            hdr_bind_group_layout,
+            tonemap_pipeline,
+            adapter_info,
+            egui_winit_state,
+            egui_renderer,
+            clear_color_b: 0.3,
+            last_frame: std::time::Instant::now(),
+            fps: 0.0,
+            clear_color: Rc::new(Cell::new(Color::BLACK)),
+            renderer,
+        };
+
+        graphics_state.rebuild_renderer();
+        graphics_state
+    }
+
+    /// Rebuilds the geometry + tonemap passes from the current pipelines,
+    /// bind groups, and buffers. Called whenever any of those change —
+    /// after `resize()`, `set_shader_source()`, or `set_instances()` — so
+    /// the passes never hold stale handles.
+    fn rebuild_renderer(&mut self) {
+        self.renderer.clear_passes();
+
+        self.renderer.add_pass(Box::new(GeometryPass {
+            pipeline: self.render_pipeline.clone(),
+            camera_bind_group: self.camera_bind_group.clone(),
+            diffuse_bind_group: self.diffuse_bind_group.clone(),
+            vertex_buffer: self.vertex_buffer.clone(),
+            index_buffer: self.index_buffer.clone(),
+            instance_buffer: self.instance_buffer.clone(),
+            num_indices: self.num_indices,
+            num_instances: self.instances.len() as u32,
+            target_view: self.hdr_view.clone(),
+            depth_view: self.depth_view.clone(),
+            clear_color: self.clear_color.clone(),
+        }));
+
+        self.renderer.add_pass(Box::new(TonemapPass {
+            pipeline: self.tonemap_pipeline.clone(),
+            hdr_bind_group: self.hdr_bind_group.clone(),
+        }));
+    }
+
+    /// Forwards a window event to egui so its widgets can consume clicks,
+    /// hovers, and keystrokes before the app's own `input()` sees them.
+    pub fn handle_egui_event(&mut self, event: &WindowEvent) -> egui_winit::EventResponse {
+        self.egui_winit_state.on_window_event(self.window, event)
+    }
+
+    /// Builds the offscreen HDR render target sized to `config`, recreated
+    /// whenever the surface is reconfigured.
+    fn create_hdr_resources(
+        device: &Device, config: &SurfaceConfiguration,
+    ) -> (WgpuTexture, TextureView, Sampler) {
+        let size = Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+
+        let hdr_texture = device.create_texture(&TextureDescriptor {
+            label: Some("HDR Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let hdr_view = hdr_texture.create_view(&TextureViewDescriptor::default());
+
+        let hdr_sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        (hdr_texture, hdr_view, hdr_sampler)
+    }
+
+    fn create_hdr_bind_group(
+        device: &Device, layout: &BindGroupLayout, hdr_view: &TextureView, hdr_sampler: &Sampler,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(hdr_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(hdr_sampler),
+                },
+            ],
+            label: Some("HDR Bind Group"),
+        })
+    }
+
+    /// Builds the fullscreen-triangle pipeline that tonemaps the HDR target
+    /// into `surface_format`. Applies the sRGB OETF manually when the
+    /// surface format isn't already an sRGB one, since in that case the
+    /// hardware won't encode it for us on write.
+    fn create_tonemap_pipeline(
+        device: &Device, surface_format: TextureFormat, hdr_bind_group_layout: &BindGroupLayout,
+    ) -> RenderPipeline {
+        let oetf = if surface_format.is_srgb() {
+            ""
+        } else {
+            "mapped = pow(mapped, vec3<f32>(1.0 / 2.2));"
+        };
+        let source = TONEMAP_SHADER_SOURCE.replace("// OETF_PLACEHOLDER", oetf);
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Tonemap Shader"),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Tonemap Pipeline Layout"),
+            bind_group_layouts: &[hdr_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Tonemap Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: surface_format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
     }
 
-    pub fn window(&self) -> &Window { &self.window }
+    fn create_instance_buffer(device: &Device, instances: &[Instance]) -> Buffer {
+        let raw: Vec<InstanceRaw> = instances.iter().map(Instance::to_raw).collect();
+
+        device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Instance Buffer"),
+            contents: bytemuck::cast_slice(&raw),
+            usage: BufferUsages::VERTEX,
+        })
+    }
+
+    /// Replaces the instances drawn each frame, rebuilding the instance
+    /// buffer. Use [`generate_grid_instances`] or a custom layout to
+    /// stress-test throughput.
+    pub fn set_instances(&mut self, instances: Vec<Instance>) {
+        self.instance_buffer = Arc::new(Self::create_instance_buffer(&self.device, &instances));
+        self.instances = instances;
+
+        self.rebuild_renderer();
+    }
+
+    /// The layout for the group-1 material bind group (a diffuse texture
+    /// and its sampler), shared by `new()` and `create_render_pipeline`.
+    fn create_texture_bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("Texture Bind Group Layout"),
+        })
+    }
+
+    /// Builds the depth texture/view sized to `config`. Called from `new()`
+    /// and re-called from `resize()` whenever the surface is reconfigured.
+    fn create_depth_texture(device: &Device, config: &SurfaceConfiguration) -> (WgpuTexture, TextureView) {
+        let size = Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+
+        let depth_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Depth Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let depth_view = depth_texture.create_view(&TextureViewDescriptor::default());
+
+        (depth_texture, depth_view)
+    }
+
+    /// The layout for the group-0 camera uniform, shared by `new()` (to
+    /// build the bind group) and `create_render_pipeline` (to build the
+    /// pipeline layout).
+    fn create_camera_bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Camera Bind Group Layout"),
+        })
+    }
+
+    /// Builds a render pipeline from WGSL source, targeting `format`. Used
+    /// both by `new()` and by [`Self::set_shader_source`] so that the
+    /// shader can be swapped out at runtime.
+    fn create_render_pipeline(
+        device: &Device, format: TextureFormat, shader_source: &str,
+        camera_bind_group_layout: &BindGroupLayout,
+        texture_bind_group_layout: &BindGroupLayout,
+    ) -> RenderPipeline {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Shader"),
+            source: ShaderSource::Wgsl(shader_source.into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, texture_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+
+    /// Rebuilds the render pipeline from new WGSL source, letting callers
+    /// experiment with shaders without restarting the app.
+    ///
+    /// Reuses the same `camera_bind_group_layout`/`texture_bind_group_layout`
+    /// objects `camera_bind_group`/`diffuse_bind_group` were built against —
+    /// wgpu checks bind-group compatibility by layout identity, not just
+    /// structure, so a freshly-created (if structurally identical) layout
+    /// here would make the next `render()` fail validation.
+    pub fn set_shader_source(&mut self, shader_source: &str) {
+        self.render_pipeline = Arc::new(Self::create_render_pipeline(
+            &self.device, HDR_FORMAT, shader_source,
+            &self.camera_bind_group_layout, &self.texture_bind_group_layout,
+        ));
+
+        self.rebuild_renderer();
+    }
+
+    pub fn window(&self) -> &Window { self.window }
+
+    /// Gives a caller access to [`Renderer::add_pass`]/[`Renderer::clear_passes`]
+    /// so it can extend or replace the geometry/tonemap passes
+    /// [`Self::rebuild_renderer`] installs. Calling `rebuild_renderer`
+    /// (indirectly, via `resize`/`set_shader_source`/`set_instances`) resets
+    /// any passes added here.
+    pub fn renderer_mut(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
@@ -101,18 +903,48 @@ impl<'a> GraphicsState<'a> {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
+            self.camera.aspect = self.config.width as f32 / self.config.height as f32;
+
+            let (depth_texture, depth_view) = Self::create_depth_texture(&self.device, &self.config);
+            self.depth_texture = depth_texture;
+            self.depth_view = Arc::new(depth_view);
+
+            let (hdr_texture, hdr_view, hdr_sampler) = Self::create_hdr_resources(&self.device, &self.config);
+            // Reuse the layout `tonemap_pipeline` was built against — wgpu
+            // matches bind groups to a pipeline by layout identity, so a
+            // freshly-created layout here would desync from the pipeline
+            // built once in `new()` and never rebuilt on resize.
+            self.hdr_bind_group = Arc::new(Self::create_hdr_bind_group(
+                &self.device, &self.hdr_bind_group_layout, &hdr_view, &hdr_sampler,
+            ));
+            self.hdr_texture = hdr_texture;
+            self.hdr_view = Arc::new(hdr_view);
+            self.hdr_sampler = hdr_sampler;
+
+            self.rebuild_renderer();
         }
     }
 
-    pub fn input(&mut self, _event: &WindowEvent) -> bool {
-        false
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        self.camera_controller.process_events(event)
     }
 
     fn update(&mut self, update_type: UpdateType) {
         match update_type {
             UpdateType::Pos(pos) => self.pos = pos,
-            UpdateType::NONE => {}
+            UpdateType::None => {}
+        }
+
+        self.camera_controller.update_camera(&mut self.camera);
+        self.camera_uniform.update_view_proj(&self.camera);
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+
+        let now = std::time::Instant::now();
+        let dt = now.duration_since(self.last_frame).as_secs_f32();
+        if dt > 0.0 {
+            self.fps = 1.0 / dt;
         }
+        self.last_frame = now;
     }
 
     fn render(&mut self) -> Result<(), SurfaceError> {
@@ -122,21 +954,68 @@ impl<'a> GraphicsState<'a> {
             label: Some("Render Encoder"),
         });
 
+        let raw_input = self.egui_winit_state.take_egui_input(self.window);
+        let adapter_info = &self.adapter_info;
+        let fps = self.fps;
+        let pos = self.pos;
+        let full_output = self.egui_winit_state.egui_ctx().clone().run(raw_input, |ctx| {
+            egui::Window::new("Debug").show(ctx, |ui| {
+                ui.label(format!("FPS: {:.1}", fps));
+                ui.label(format!("Cursor: ({}, {})", pos.0, pos.1));
+                ui.label(format!("Adapter: {} ({:?})", adapter_info.name, adapter_info.backend));
+                ui.add(egui::Slider::new(&mut self.clear_color_b, 0.0..=1.0).text("Clear Blue"));
+                ui.add(egui::Slider::new(&mut self.camera.fovy, 10.0..=120.0).text("FOV"));
+                ui.add(egui::Slider::new(&mut self.camera.znear, 0.01..=5.0).text("Near"));
+                ui.add(egui::Slider::new(&mut self.camera.zfar, 10.0..=500.0).text("Far"));
+            });
+        });
+        self.egui_winit_state.handle_platform_output(self.window, full_output.platform_output.clone());
+
+        let clipped_primitives = self.egui_winit_state.egui_ctx().tessellate(
+            full_output.shapes.clone(), full_output.pixels_per_point,
+        );
+
+        for (id, delta) in &full_output.textures_delta.set {
+            self.egui_renderer.update_texture(&self.device, &self.queue, *id, delta);
+        }
+
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [self.config.width, self.config.height],
+            pixels_per_point: self.window.scale_factor() as f32,
+        };
+        self.egui_renderer.update_buffers(
+            &self.device, &self.queue, &mut encoder, &clipped_primitives, &screen_descriptor,
+        );
+
+        // The geometry and tonemap passes are owned by `self.renderer` now;
+        // only the dynamic clear color (cursor position + egui slider) still
+        // needs to be pushed in before each frame.
+        self.clear_color.set(Color {
+            r: self.pos.0 as f64 / self.size.width as f64,
+            g: self.pos.1 as f64 / self.size.height as f64,
+            b: self.clear_color_b as f64,
+            a: 1.0,
+        });
+        self.renderer.render(&view);
+
+        // Egui is driven by a per-frame closure that needs `&mut self`, which
+        // doesn't fit the stateless `RenderPass::record(&self, ..)` signature,
+        // so it stays outside `self.renderer` and submits through its own
+        // encoder instead of being folded into the pass list above. That
+        // means this frame does two `queue.submit`s, not the single one
+        // `Renderer::render` documents — the egui pass loads (doesn't clear)
+        // `view`, so it only looks correct because this submit is ordered
+        // after `self.renderer.render(&view)` above. There's no explicit
+        // wait between the two; wgpu's queue submission order is what keeps
+        // egui drawing on top of the tonemapped frame rather than a race.
         {
-            let _render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some("Render Pass"),
+            let mut egui_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Egui Pass"),
                 color_attachments: &[Some(RenderPassColorAttachment {
                     view: &view,
                     resolve_target: None,
                     ops: Operations {
-                        load: LoadOp::Clear(Color {
-                            // r: 0.1,
-                            r: self.pos.0 as f64 / self.size.width as f64,
-                            // g: 0.2,
-                            g: self.pos.1 as f64 / self.size.height as f64,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: LoadOp::Load,
                         store: StoreOp::Store,
                     },
                 })],
@@ -144,6 +1023,12 @@ impl<'a> GraphicsState<'a> {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
+
+            self.egui_renderer.render(&mut egui_pass, &clipped_primitives, &screen_descriptor);
+        }
+
+        for id in &full_output.textures_delta.free {
+            self.egui_renderer.free_texture(id);
         }
 
         self.queue.submit(iter::once(encoder.finish()));
@@ -154,7 +1039,14 @@ impl<'a> GraphicsState<'a> {
 }
 
 pub async fn run() {
-    env_logger::init();
+    cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+            console_log::init_with_level(log::Level::Warn).expect("Couldn't initialize logger");
+        } else {
+            env_logger::init();
+        }
+    }
 
     let event_loop = EventLoop::new().unwrap();
 
@@ -163,15 +1055,37 @@ pub async fn run() {
         .with_inner_size(LogicalSize::new(512.0, 512.0))
         .build(&event_loop).unwrap();
 
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::WindowExtWebSys;
+
+        web_sys::window()
+            .and_then(|win| win.document())
+            .and_then(|doc| {
+                let canvas = web_sys::Element::from(window.canvas()?);
+                doc.body()?.append_child(&canvas).ok()
+            })
+            .expect("Couldn't append canvas to document body.");
+    }
+
     let mut graphics_state: GraphicsState = GraphicsState::new(&window).await;
 
+    // The browser reports the canvas at size 0 until it's attached to the
+    // DOM and laid out, so the surface isn't actually ready to render into
+    // until the first real `Resized` event arrives; native windows report
+    // their true size up front, so this settles on the very first frame.
+    let mut surface_configured = false;
+
     info!("Starting up window.");
 
     let _ = event_loop.run(move | event, control_flow | {
         match event {
             Event::WindowEvent {
                 window_id, ref event
-            } if window_id == graphics_state.window().id() => if !graphics_state.input(event) {
+            } if window_id == graphics_state.window().id() => {
+                let egui_consumed = graphics_state.handle_egui_event(event).consumed;
+
+                if !egui_consumed && !graphics_state.input(event) {
                 match event {
                     WindowEvent::CloseRequested | WindowEvent::KeyboardInput {
                         event: KeyEvent {
@@ -184,17 +1098,18 @@ pub async fn run() {
 
                     WindowEvent::Resized(physical_size) => {
                         info!("resizing");
+                        surface_configured = true;
                         graphics_state.resize(*physical_size);
                     }
 
                     WindowEvent::RedrawRequested => {
                         graphics_state.window().request_redraw();
 
-                        // if !surface_configured {
-                        //     return;
-                        // }
+                        if !surface_configured {
+                            return;
+                        }
 
-                        graphics_state.update(UpdateType::NONE);
+                        graphics_state.update(UpdateType::None);
 
                         match graphics_state.render() {
                             Ok(_) => {}
@@ -219,7 +1134,7 @@ pub async fn run() {
                     }
 
                     WindowEvent::CursorMoved {
-                        device_id,
+                        device_id: _,
                         position,
                     } => {
                         error!("position: x: {}, y: {}", position.x, position.y);
@@ -235,6 +1150,7 @@ pub async fn run() {
                     // }
                     _ => {}
                 }
+                }
             }
 
 
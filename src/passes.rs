@@ -0,0 +1,97 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use wgpu::{
+    Device, CommandEncoder, TextureView, RenderPipeline, BindGroup, Buffer, Color,
+    RenderPassDescriptor, RenderPassColorAttachment, RenderPassDepthStencilAttachment,
+    Operations, LoadOp, StoreOp, IndexFormat,
+};
+
+use crate::renderer::RenderPass;
+
+/// Lets the clear color keep changing every frame (driven by the cursor
+/// position and an egui slider) without rebuilding the pass list each time.
+pub type SharedClearColor = Rc<Cell<Color>>;
+
+/// Clears and draws the instanced mesh into an offscreen HDR target with
+/// depth testing. Always targets `target_view`/`depth_view`, ignoring the
+/// swapchain view `record` is handed.
+pub struct GeometryPass {
+    pub pipeline: Arc<RenderPipeline>,
+    pub camera_bind_group: Arc<BindGroup>,
+    pub diffuse_bind_group: Arc<BindGroup>,
+    pub vertex_buffer: Arc<Buffer>,
+    pub index_buffer: Arc<Buffer>,
+    pub instance_buffer: Arc<Buffer>,
+    pub num_indices: u32,
+    pub num_instances: u32,
+    pub target_view: Arc<TextureView>,
+    pub depth_view: Arc<TextureView>,
+    pub clear_color: SharedClearColor,
+}
+
+impl RenderPass for GeometryPass {
+    fn record(&self, encoder: &mut CommandEncoder, _view: &TextureView, _device: &Device) {
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Geometry Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &self.target_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(self.clear_color.get()),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        pass.set_bind_group(1, &self.diffuse_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint16);
+        pass.draw_indexed(0..self.num_indices, 0, 0..self.num_instances);
+    }
+}
+
+/// Resolves the HDR target (sampled via `hdr_bind_group`) into the
+/// swapchain view `record` is handed, tonemapping and OETF-correcting it
+/// with a fullscreen triangle.
+pub struct TonemapPass {
+    pub pipeline: Arc<RenderPipeline>,
+    pub hdr_bind_group: Arc<BindGroup>,
+}
+
+impl RenderPass for TonemapPass {
+    fn record(&self, encoder: &mut CommandEncoder, view: &TextureView, _device: &Device) {
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.hdr_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
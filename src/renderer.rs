@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+use wgpu::{ Device, Queue, CommandEncoder, CommandEncoderDescriptor, TextureView, Maintain };
+
+/// One recorded step of a frame. Implementors own whatever pipeline, bind
+/// groups, and buffers they need and record directly into the shared
+/// encoder; `Renderer::render` runs every registered pass in order before
+/// a single `queue.submit`.
+pub trait RenderPass {
+    fn record(&self, encoder: &mut CommandEncoder, view: &TextureView, device: &Device);
+}
+
+const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+
+/// Composes a frame out of registered [`RenderPass`]es instead of a single
+/// hardcoded `render()` body, so a host application can add/remove passes
+/// (a clear pass, a geometry pass, an overlay pass, ...) without touching
+/// this type.
+pub struct Renderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    passes: Vec<Box<dyn RenderPass>>,
+    frames_in_flight: usize,
+    submitted_frames: Arc<AtomicUsize>,
+}
+
+impl Renderer {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>) -> Self {
+        Self {
+            device,
+            queue,
+            passes: Vec::new(),
+            frames_in_flight: DEFAULT_FRAMES_IN_FLIGHT,
+            submitted_frames: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn add_pass(&mut self, pass: Box<dyn RenderPass>) {
+        self.passes.push(pass);
+    }
+
+    pub fn clear_passes(&mut self) {
+        self.passes.clear();
+    }
+
+    /// Caps how many submitted frames are allowed to be outstanding on the
+    /// GPU at once (default 2). `render` blocks on [`Device::poll`] before
+    /// recording a new frame once this many are still in flight, bounding
+    /// how far the CPU can race ahead of the GPU.
+    pub fn set_frames_in_flight(&mut self, frames_in_flight: usize) {
+        self.frames_in_flight = frames_in_flight.max(1);
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames_in_flight
+    }
+
+    /// Records every registered pass into one encoder, in order, then
+    /// submits them together.
+    pub fn render(&self, view: &TextureView) {
+        while self.submitted_frames.load(Ordering::Acquire) >= self.frames_in_flight {
+            self.device.poll(Maintain::Wait);
+        }
+
+        let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Renderer Encoder"),
+        });
+
+        for pass in &self.passes {
+            pass.record(&mut encoder, view, &self.device);
+        }
+
+        self.submitted_frames.fetch_add(1, Ordering::AcqRel);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let submitted_frames = self.submitted_frames.clone();
+        self.queue.on_submitted_work_done(move || {
+            submitted_frames.fetch_sub(1, Ordering::AcqRel);
+        });
+    }
+}
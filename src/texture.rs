@@ -0,0 +1,80 @@
+use image::GenericImageView;
+use wgpu::{
+    Device, Queue, Texture as WgpuTexture, TextureView, Sampler, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, Extent3d, TextureViewDescriptor,
+    SamplerDescriptor, AddressMode, FilterMode, ImageCopyTexture, ImageDataLayout,
+    Origin3d, TextureAspect,
+};
+
+/// A decoded image uploaded to the GPU, plus the view/sampler pair needed to
+/// bind it into a material bind group.
+pub struct Texture {
+    // Never read directly, but must outlive `view`/`sampler`.
+    #[allow(dead_code)]
+    pub texture: WgpuTexture,
+    pub view: TextureView,
+    pub sampler: Sampler,
+}
+
+impl Texture {
+    pub fn from_bytes(
+        device: &Device, queue: &Queue, bytes: &[u8], label: &str,
+    ) -> image::ImageResult<Self> {
+        let img = image::load_from_memory(bytes)?;
+
+        Ok(Self::from_image(device, queue, &img, Some(label)))
+    }
+
+    pub fn from_image(
+        device: &Device, queue: &Queue, img: &image::DynamicImage, label: Option<&str>,
+    ) -> Self {
+        let rgba = img.to_rgba8();
+        let dimensions = img.dimensions();
+
+        let size = Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &rgba,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * dimensions.0),
+                rows_per_image: Some(dimensions.1),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { texture, view, sampler }
+    }
+}